@@ -0,0 +1,78 @@
+//! Incrementally constructs a [`Cif`](../struct.Cif.html).
+
+use super::{Cif, Type};
+
+/// Builds up a [`Cif`](../struct.Cif.html) one argument at a time.
+///
+/// # Examples
+///
+/// ```
+/// use libffi::middle::{Builder, Type};
+///
+/// let cif = Builder::new()
+///     .arg(Type::i64())
+///     .arg(Type::i64())
+///     .res(Type::i64())
+///     .into_cif();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    args: Vec<Type>,
+    result: Option<Type>,
+    // `Some(n)` once [`variadic`](#method.variadic) has been called,
+    // where `n` is the number of fixed arguments added so far.
+    nfixedargs: Option<usize>,
+}
+
+impl Builder {
+    /// Creates a new, empty `Builder`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds an argument type.
+    pub fn arg(mut self, type_: Type) -> Self {
+        self.args.push(type_);
+        self
+    }
+
+    /// Adds several argument types, in order.
+    pub fn args<I>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = Type>,
+    {
+        self.args.extend(types);
+        self
+    }
+
+    /// Sets the result type. Defaults to [`Type::void`](struct.Type.html#method.void)
+    /// if never called.
+    pub fn res(mut self, type_: Type) -> Self {
+        self.result = Some(type_);
+        self
+    }
+
+    /// Marks the end of the function’s fixed arguments: any arguments
+    /// added via [`arg`](#method.arg)/[`args`](#method.args) *after*
+    /// this call are treated as the variadic (`...`) arguments of a
+    /// single call, and [`into_cif`](#method.into_cif) will build the
+    /// `Cif` with [`Cif::new_variadic`](../struct.Cif.html#method.new_variadic).
+    pub fn variadic(mut self) -> Self {
+        self.nfixedargs = Some(self.args.len());
+        self
+    }
+
+    /// Builds the [`Cif`](../struct.Cif.html).
+    pub fn into_cif(self) -> Cif {
+        let result = self.result.unwrap_or_else(Type::void);
+
+        match self.nfixedargs {
+            None => Cif::new(self.args, result),
+            Some(nfixedargs) => {
+                let mut args = self.args;
+                let var_args = args.split_off(nfixedargs);
+                Cif::new_variadic(args, var_args, result)
+            }
+        }
+    }
+}
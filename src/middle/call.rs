@@ -0,0 +1,124 @@
+//! A dynamically-typed call dispatcher.
+//!
+//! Building a call by hand means constructing `&[Arg]` out of
+//! correctly-typed references whose lifetimes must outlive the
+//! [`Cif::call`](../struct.Cif.html#method.call) itself — easy to get
+//! wrong, since [`Arg::new`](../struct.Arg.html#method.new) erases the
+//! argument's type to a bare `*mut c_void`, and nothing stops a caller
+//! from passing a reference to a temporary that's already been dropped
+//! by the time the call happens. [`CallBuilder`] instead owns each
+//! argument's value (boxed) alongside its [`Type`], so the pointers
+//! handed to libffi are guaranteed to stay alive for the call's
+//! duration.
+
+use std::any::Any;
+use std::os::raw::c_void;
+
+use super::{Arg, Cif, CodePtr, Type};
+
+/// Owns a list of boxed argument values alongside their [`Type`]s,
+/// keeping them alive for as long as the `ArgList` itself lives.
+#[derive(Default)]
+pub struct ArgList {
+    entries: Vec<(Type, Box<dyn Any>)>,
+}
+
+impl ArgList {
+    /// Creates an empty argument list.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends an argument, pairing `value` with its `Type`.
+    pub fn push<T: 'static>(&mut self, type_: Type, value: T) {
+        self.entries.push((type_, Box::new(value)));
+    }
+
+    /// The `Type` of each argument, in order.
+    fn types(&self) -> Vec<Type> {
+        self.entries.iter().map(|(t, _)| t.clone()).collect()
+    }
+
+    /// Coerces each owned argument value into an [`Arg`], in order. The
+    /// returned `Arg`s borrow from `self` and must not outlive it.
+    fn as_args(&self) -> Vec<Arg> {
+        self.entries
+            .iter()
+            .map(|(_, v)| Arg(v.as_ref() as *const dyn Any as *mut c_void))
+            .collect()
+    }
+
+    /// Builds a [`Cif`] from the collected argument types and `result`,
+    /// then invokes `code` with the collected argument values, returning
+    /// the result.
+    ///
+    /// # Safety
+    ///
+    /// There is no checking that `result` and the argument `Type`s
+    /// passed to [`push`](#method.push) match the actual calling
+    /// convention and types of the function at `code`.
+    pub unsafe fn invoke<R>(&self, code: CodePtr, result: Type) -> R {
+        let cif = Cif::new(self.types().into_iter(), result);
+        let arg_ptrs = self.as_args();
+
+        cif.call(code, &arg_ptrs)
+    }
+}
+
+impl From<ArgList> for CallBuilder {
+    fn from(args: ArgList) -> Self {
+        CallBuilder { args }
+    }
+}
+
+/// Incrementally builds a call to a foreign function, pairing each
+/// argument's value with its [`Type`] so that an [`Arg`] can never
+/// dangle.
+///
+/// # Examples
+///
+/// ```
+/// use libffi::middle::{CallBuilder, CodePtr, Type};
+///
+/// extern "C" fn add(x: i64, y: i64) -> i64 {
+///     x + y
+/// }
+///
+/// let n: i64 = unsafe {
+///     CallBuilder::new()
+///         .arg(Type::i64(), 5i64)
+///         .arg(Type::i64(), 6i64)
+///         .invoke(CodePtr(add as *mut _), Type::i64())
+/// };
+/// assert_eq!(11, n);
+/// ```
+#[derive(Default)]
+pub struct CallBuilder {
+    args: ArgList,
+}
+
+impl CallBuilder {
+    /// Creates a new, empty `CallBuilder`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds an argument, pairing `value` with its `Type`.
+    pub fn arg<T: 'static>(mut self, type_: Type, value: T) -> Self {
+        self.args.push(type_, value);
+        self
+    }
+
+    /// Builds a [`Cif`] from the collected argument types and `result`,
+    /// then invokes `code` with the collected argument values, returning
+    /// the result.
+    ///
+    /// # Safety
+    ///
+    /// There is no checking that `result` and the argument `Type`s
+    /// passed to [`arg`](#method.arg) match the actual calling
+    /// convention and types of the function at `code`.
+    pub unsafe fn invoke<R>(self, code: CodePtr, result: Type) -> R {
+        self.args.invoke(code, result)
+    }
+}
@@ -0,0 +1,54 @@
+//! A unified error type for the fallible `try_*` constructors in this
+//! module.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use crate::low;
+
+/// An error preparing a [`Cif`](../struct.Cif.html) or
+/// [`Closure`](../struct.Closure.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// `ffi_prep_cif`/`ffi_prep_cif_var`/`ffi_prep_closure_loc` rejected
+    /// the CIF, e.g. because of a bad ABI or an unsupported typedef.
+    Low(low::Error),
+    /// `ffi_closure_alloc` returned a null pointer.
+    AllocFailed,
+    /// A vararg passed to [`Cif::new_variadic`](../struct.Cif.html#method.new_variadic)
+    /// had a `Type` (`f32`, or an integer narrower than `int`) that the C
+    /// variadic ABI requires to be promoted before passing.
+    ///
+    /// The `middle` layer passes every argument by pointer (see
+    /// [`Arg`](../struct.Arg.html)), and that pointer is set up by the
+    /// caller to match the *value*'s real type. A vararg's `Type` can't
+    /// simply be rewritten to its promoted form, because the pointer
+    /// behind it would still reference a narrower value, and libffi would
+    /// read past the end of it. So rather than silently promoting (and
+    /// reading garbage), this is rejected; the caller must itself pass an
+    /// already-promoted value (e.g. an `f64` in place of an `f32`, or an
+    /// `i32`/`u32` in place of a sub-`int`).
+    UnpromotedVararg,
+}
+
+impl From<low::Error> for Error {
+    fn from(err: low::Error) -> Self {
+        Error::Low(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Low(err) => write!(f, "{:?}", err),
+            Error::AllocFailed => write!(f, "ffi_closure_alloc returned a null pointer"),
+            Error::UnpromotedVararg => write!(
+                f,
+                "vararg type must already be promoted (f64 instead of f32, \
+                 i32/u32 instead of a sub-int type)"
+            ),
+        }
+    }
+}
+
+impl StdError for Error {}
@@ -13,6 +13,8 @@
 use std::any::Any;
 use std::os::raw::c_void;
 use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
 
 use crate::low;
 pub use crate::low::{Callback, CallbackMut, CodePtr,
@@ -26,6 +28,12 @@ pub use types::Type;
 mod builder;
 pub use builder::Builder;
 
+mod error;
+pub use error::Error;
+
+mod call;
+pub use call::{ArgList, CallBuilder};
+
 /// Contains an untyped pointer to a function argument.
 ///
 /// When calling a function via a [CIF](struct.Cif.html), each argument
@@ -86,6 +94,9 @@ pub struct Cif {
     cif:    low::ffi_cif,
     args:   types::TypeArray,
     result: Type,
+    // `Some(n)` when this CIF was prepared with `ffi_prep_cif_var`, where
+    // `n` is the number of fixed (non-variadic) arguments.
+    nfixedargs: Option<usize>,
 }
 
 // To clone a Cif we need to clone the types and then make sure the new
@@ -96,6 +107,7 @@ impl Clone for Cif {
             cif:    self.cif,
             args:   self.args.clone(),
             result: self.result.clone(),
+            nfixedargs: self.nfixedargs,
         };
 
         copy.cif.arg_types = copy.args.as_raw_ptr();
@@ -117,9 +129,33 @@ impl Cif {
         where I: IntoIterator<Item=Type>,
               I::IntoIter: ExactSizeIterator<Item=Type>
     {
-        let args = args.into_iter();
+        Self::try_new(args, result).expect("low::prep_cif")
+    }
+
+    /// The fallible counterpart to [`new`](#method.new).
+    ///
+    /// Returns an [`Error`](enum.Error.html) instead of panicking when
+    /// libffi rejects the CIF, e.g. because of an unsupported typedef —
+    /// useful when the ABI or argument types are chosen at runtime (for
+    /// example by a scripting bridge) rather than known up front.
+    pub fn try_new<I>(args: I, result: Type) -> Result<Self, Error>
+        where I: IntoIterator<Item=Type>,
+              I::IntoIter: ExactSizeIterator<Item=Type>
+    {
+        Self::try_from_type_array(types::TypeArray::new(args.into_iter()), result)
+    }
+
+    /// Creates a new CIF from an already-built
+    /// [`TypeArray`](types/struct.TypeArray.html) of argument types and
+    /// a result type.
+    pub fn from_type_array(args: types::TypeArray, result: Type) -> Self {
+        Self::try_from_type_array(args, result).expect("low::prep_cif")
+    }
+
+    /// The fallible counterpart to
+    /// [`from_type_array`](#method.from_type_array).
+    pub fn try_from_type_array(args: types::TypeArray, result: Type) -> Result<Self, Error> {
         let nargs = args.len();
-        let args = types::TypeArray::new(args);
         let mut cif: low::ffi_cif = Default::default();
 
         unsafe {
@@ -128,11 +164,72 @@ impl Cif {
                           nargs,
                           result.as_raw_ptr(),
                           args.as_raw_ptr())
-        }.expect("low::prep_cif");
+        }?;
 
         // Note that cif retains references to args and result,
         // which is why we hold onto them here.
-        Cif { cif, args, result }
+        Ok(Cif { cif, args, result, nfixedargs: None })
+    }
+
+    /// Creates a new CIF for a variadic function, e.g. one declared with
+    /// a trailing `...` in C such as `printf`.
+    ///
+    /// `fixed_args` are the function’s ordinary, fixed-position
+    /// arguments; `var_args` describes the types of the arguments passed
+    /// for this particular call’s `...` tail. Each vararg must already be
+    /// promoted per the C variadic ABI — see
+    /// [`Error::UnpromotedVararg`] for why this constructor can't perform
+    /// that promotion itself. This constructor panics if a `var_args`
+    /// entry isn't already promoted; use
+    /// [`try_new_variadic`](#method.try_new_variadic) to handle that case
+    /// without panicking.
+    ///
+    /// Takes ownership of the argument and result
+    /// [`Type`](types/struct.Type.html)s, because the resulting `Cif`
+    /// retains references to them.
+    pub fn new_variadic<I, J>(fixed_args: I, var_args: J, result: Type) -> Self
+        where I: IntoIterator<Item=Type>,
+              I::IntoIter: ExactSizeIterator<Item=Type>,
+              J: IntoIterator<Item=Type>,
+              J::IntoIter: ExactSizeIterator<Item=Type>
+    {
+        Self::try_new_variadic(fixed_args, var_args, result).expect("low::prep_cif_var")
+    }
+
+    /// The fallible counterpart to [`new_variadic`](#method.new_variadic).
+    ///
+    /// Returns `Err(Error::UnpromotedVararg)` rather than panicking when
+    /// a `var_args` entry isn't already promoted per the C variadic ABI.
+    pub fn try_new_variadic<I, J>(fixed_args: I, var_args: J, result: Type)
+        -> Result<Self, Error>
+        where I: IntoIterator<Item=Type>,
+              I::IntoIter: ExactSizeIterator<Item=Type>,
+              J: IntoIterator<Item=Type>,
+              J::IntoIter: ExactSizeIterator<Item=Type>
+    {
+        let fixed_args = fixed_args.into_iter();
+        let nfixedargs = fixed_args.len();
+
+        let var_args: Vec<Type> = var_args.into_iter().collect();
+        for var_arg in &var_args {
+            var_arg.check_vararg_promoted()?;
+        }
+
+        let args = types::TypeArray::new(fixed_args.chain(var_args));
+        let ntotalargs = args.len();
+
+        let mut cif: low::ffi_cif = Default::default();
+
+        unsafe {
+            low::prep_cif_var(&mut cif,
+                              low::ffi_abi_FFI_DEFAULT_ABI,
+                              nfixedargs,
+                              ntotalargs,
+                              result.as_raw_ptr(),
+                              args.as_raw_ptr())
+        }?;
+
+        Ok(Cif { cif, args, result, nfixedargs: Some(nfixedargs) })
     }
 
     /// Calls a function with the given arguments.
@@ -149,9 +246,41 @@ impl Cif {
         assert_eq!(self.cif.nargs as usize, args.len(),
                    "Cif::call: passed wrong number of arguments");
 
-        low::call::<R>(&self.cif as *const _ as *mut _,
-                       fun,
-                       args.as_ptr() as *mut *mut c_void)
+        // libffi requires integer/bool results narrower than a machine
+        // register (`ffi_arg`) to be written into a register-sized,
+        // register-aligned buffer — it sign/zero-extends the real value
+        // to fill the whole register. Handing it a bare, possibly
+        // 1-byte `R` slot is unsound: libffi may write the full
+        // register width and corrupt adjacent memory. When that's the
+        // case we call into a register-sized buffer instead and narrow
+        // the result back down afterwards.
+        if mem::size_of::<R>() < mem::size_of::<low::ffi_arg>() && self.result.is_integer() {
+            let widened: low::ffi_arg =
+                low::call(&self.cif as *const _ as *mut _,
+                         fun,
+                         args.as_ptr() as *mut *mut c_void);
+
+            // The real value occupies the low-order `size_of::<R>()`
+            // bytes of the register — the *first* bytes in native order
+            // on a little-endian target, but the *last* ones on
+            // big-endian, so which end we copy from depends on target
+            // endianness.
+            let bytes = widened.to_ne_bytes();
+            #[cfg(target_endian = "big")]
+            let low_order = &bytes[bytes.len() - mem::size_of::<R>()..];
+            #[cfg(target_endian = "little")]
+            let low_order = &bytes[..mem::size_of::<R>()];
+
+            let mut result = mem::MaybeUninit::<R>::uninit();
+            ptr::copy_nonoverlapping(low_order.as_ptr(),
+                                     result.as_mut_ptr() as *mut u8,
+                                     mem::size_of::<R>());
+            result.assume_init()
+        } else {
+            low::call::<R>(&self.cif as *const _ as *mut _,
+                           fun,
+                           args.as_ptr() as *mut *mut c_void)
+        }
     }
 
     /// Sets the CIF to use the given calling convention.
@@ -255,24 +384,48 @@ impl<'a> Closure<'a> {
     pub fn new<U, R>(cif:      Cif,
                      callback: Callback<U, R>,
                      userdata: &'a U) -> Self
+    {
+        Self::try_new(cif, callback, userdata).expect("Closure::new")
+    }
+
+    /// The fallible counterpart to [`new`](#method.new).
+    ///
+    /// Returns an [`Error`](enum.Error.html) instead of panicking if
+    /// `ffi_closure_alloc` fails to allocate, or if libffi rejects the
+    /// CIF's calling convention or types.
+    pub fn try_new<U, R>(cif:      Cif,
+                         callback: Callback<U, R>,
+                         userdata: &'a U) -> Result<Self, Error>
     {
         let cif = Box::new(cif);
         let (alloc, code) = low::closure_alloc();
 
-        unsafe {
+        if alloc.is_null() {
+            return Err(Error::AllocFailed);
+        }
+
+        let prepped = unsafe {
             low::prep_closure(alloc,
                               cif.as_raw_ptr(),
                               callback,
                               userdata as *const U,
-                              code).unwrap();
+                              code)
+        };
+
+        if let Err(err) = prepped {
+            // `alloc` is only freed by `Drop`, and we're not constructing a
+            // `Closure` to drop — free it ourselves so a rejected CIF
+            // doesn't leak the executable closure page.
+            unsafe { low::closure_free(alloc); }
+            return Err(err.into());
         }
 
-        Closure {
+        Ok(Closure {
             _cif:    cif,
             alloc,
             code,
             _marker: PhantomData,
-        }
+        })
     }
 
     /// Creates a new closure with mutable userdata.
@@ -291,24 +444,44 @@ impl<'a> Closure<'a> {
     pub fn new_mut<U, R>(cif:      Cif,
                          callback: CallbackMut<U, R>,
                          userdata: &'a mut U) -> Self
+    {
+        Self::try_new_mut(cif, callback, userdata).expect("Closure::new_mut")
+    }
+
+    /// The fallible counterpart to [`new_mut`](#method.new_mut).
+    pub fn try_new_mut<U, R>(cif:      Cif,
+                             callback: CallbackMut<U, R>,
+                             userdata: &'a mut U) -> Result<Self, Error>
     {
         let cif = Box::new(cif);
         let (alloc, code) = low::closure_alloc();
 
-        unsafe {
+        if alloc.is_null() {
+            return Err(Error::AllocFailed);
+        }
+
+        let prepped = unsafe {
             low::prep_closure_mut(alloc,
                                   cif.as_raw_ptr(),
                                   callback,
                                   userdata as *mut U,
-                                  code).unwrap();
+                                  code)
+        };
+
+        if let Err(err) = prepped {
+            // See the matching comment in `try_new`: free `alloc`
+            // ourselves, since no `Closure` is being constructed to do it
+            // via `Drop`.
+            unsafe { low::closure_free(alloc); }
+            return Err(err.into());
         }
 
-        Closure {
+        Ok(Closure {
             _cif:    cif,
             alloc,
             code,
             _marker: PhantomData,
-        }
+        })
     }
 
     /// Obtains the callable code pointer for a closure.
@@ -336,6 +509,75 @@ impl<'a> Closure<'a> {
     }
 }
 
+/// A [`Closure`](struct.Closure.html) variant that may be registered
+/// with, and invoked from, any thread.
+///
+/// libffi’s executable trampoline is itself thread-safe to call; what
+/// `Closure` cannot express is whether the *userdata* it closes over is
+/// safe to share across threads, which rules out registering a
+/// `Closure` with C libraries that invoke callbacks from another thread
+/// (event loops, thread pools, signal handlers, and the like — a very
+/// common FFI pattern). `SyncClosure` requires `'static` userdata bounded
+/// by `Send + Sync`, and in turn implements `Send`/`Sync` itself, so its
+/// [`code_ptr`](#method.code_ptr) may be handed to foreign code that
+/// calls it from arbitrary threads.
+///
+/// There is deliberately no mutable-userdata constructor analogous to
+/// [`Closure::new_mut`](struct.Closure.html#method.new_mut): libffi may
+/// invoke the callback from multiple threads, possibly concurrently, and
+/// handing out a plain `&mut U` to each invocation would let two threads
+/// produce two live `&mut U`s to the same data — a data race enforceable
+/// only by convention, not by the type system. Reach for an explicitly
+/// synchronized userdata type (e.g. `Mutex<U>` or an atomic) accessed
+/// through [`new`](#method.new)'s shared-reference callback instead.
+#[derive(Debug)]
+pub struct SyncClosure(Closure<'static>);
+
+// Safe because construction requires `U: Send + Sync`, and the callback
+// only ever receives a shared `&U` — exactly the guarantee `Sync`
+// promises.
+unsafe impl Send for SyncClosure {}
+unsafe impl Sync for SyncClosure {}
+
+impl SyncClosure {
+    /// Creates a new closure with immutable, thread-shareable userdata.
+    ///
+    /// `U: Send + Sync` is required because `callback` may be invoked
+    /// concurrently, from any thread, for as long as the closure is
+    /// alive.
+    pub fn new<U, R>(cif:      Cif,
+                     callback: Callback<U, R>,
+                     userdata: &'static U) -> Self
+        where U: Send + Sync
+    {
+        SyncClosure(Closure::new(cif, callback, userdata))
+    }
+
+    /// Obtains the callable code pointer for a closure.
+    ///
+    /// # Safety
+    ///
+    /// The result needs to be transmuted to the correct type before
+    /// it can be called. If the type is wrong then undefined behavior
+    /// will result.
+    pub fn code_ptr(&self) -> &unsafe extern "C" fn() {
+        self.0.code_ptr()
+    }
+
+    /// Transmutes the callable code pointer for a closure to a reference
+    /// to any type. This is intended to be used to transmute it to its
+    /// correct function type in order to call it.
+    ///
+    /// # Safety
+    ///
+    /// This method allows transmuting to a reference to *any* sized type,
+    /// and cannot check whether the code pointer actually has that type.
+    /// If the type is wrong then undefined behavior will result.
+    pub unsafe fn instantiate_code_ptr<T>(&self) -> &T {
+        self.0.instantiate_code_ptr()
+    }
+}
+
 /// The type of callback invoked by a
 /// [`ClosureOnce`](struct.ClosureOnce.html).
 pub type CallbackOnce<U, R> = CallbackMut<Option<U>, R>;
@@ -378,25 +620,44 @@ impl ClosureOnce {
                           callback: CallbackOnce<U, R>,
                           userdata: U)
                           -> Self
+    {
+        Self::try_new(cif, callback, userdata).expect("ClosureOnce::new")
+    }
+
+    /// The fallible counterpart to [`new`](#method.new).
+    pub fn try_new<U: Any, R>(cif:      Cif,
+                              callback: CallbackOnce<U, R>,
+                              userdata: U)
+                              -> Result<Self, Error>
     {
         let _cif = Box::new(cif);
         let _userdata = Box::new(Some(userdata)) as Box<dyn Any>;
         let (alloc, code) = low::closure_alloc();
 
-        assert!(!alloc.is_null(), "closure_alloc: returned null");
+        if alloc.is_null() {
+            return Err(Error::AllocFailed);
+        }
 
-        {
+        let prepped = {
             let borrow = _userdata.downcast_ref::<Option<U>>().unwrap();
             unsafe {
                 low::prep_closure_mut(alloc,
                                       _cif.as_raw_ptr(),
                                       callback,
                                       borrow as *const _ as *mut _,
-                                      code).unwrap();
+                                      code)
             }
+        };
+
+        if let Err(err) = prepped {
+            // See the matching comment in `Closure::try_new`: free `alloc`
+            // ourselves, since no `ClosureOnce` is being constructed to do
+            // it via `Drop`.
+            unsafe { low::closure_free(alloc); }
+            return Err(err.into());
         }
 
-        ClosureOnce { alloc, code, _cif, _userdata }
+        Ok(ClosureOnce { alloc, code, _cif, _userdata })
     }
 
     /// Obtains the callable code pointer for a closure.
@@ -448,6 +709,110 @@ mod test {
         n + m
     }
 
+    #[test]
+    fn variadic_call() {
+        let cif = Cif::new_variadic(vec![Type::i64()].into_iter(),
+                                    vec![Type::i64()].into_iter(),
+                                    Type::i64());
+        let n: i64 = unsafe {
+            cif.call(CodePtr(add_it as *mut c_void), &[arg(&5i64), arg(&7i64)])
+        };
+
+        assert_eq!(12, n);
+    }
+
+    #[test]
+    fn variadic_rejects_unpromoted_vararg() {
+        let err = Cif::try_new_variadic(Vec::<Type>::new().into_iter(),
+                                        vec![Type::f32()].into_iter(),
+                                        Type::f64())
+            .unwrap_err();
+
+        assert_eq!(Error::UnpromotedVararg, err);
+    }
+
+    #[test]
+    fn narrow_result() {
+        let cif = Cif::new(vec![Type::i64()].into_iter(), Type::u8());
+        let n: u8 = unsafe {
+            cif.call(CodePtr(truncate_to_u8 as *mut c_void), &[arg(&0x1234i64)])
+        };
+        assert_eq!(0x34, n);
+
+        let cif = Cif::new(vec![Type::i64()].into_iter(), Type::i16());
+        let n: i16 = unsafe {
+            cif.call(CodePtr(negate_to_i16 as *mut c_void), &[arg(&5i64)])
+        };
+        assert_eq!(-5, n);
+
+        let cif = Cif::new(vec![Type::i64()].into_iter(), Type::u8());
+        let b: u8 = unsafe {
+            cif.call(CodePtr(is_zero as *mut c_void), &[arg(&0i64)])
+        };
+        assert_eq!(1, b);
+    }
+
+    extern "C" fn truncate_to_u8(n: i64) -> u8 {
+        n as u8
+    }
+
+    extern "C" fn negate_to_i16(n: i64) -> i16 {
+        -(n as i16)
+    }
+
+    extern "C" fn is_zero(n: i64) -> bool {
+        n == 0
+    }
+
+    #[test]
+    fn struct_layout() {
+        // Mirrors `struct { u8 a; i32 b; }`, which on every mainstream
+        // ABI pads `a` out to `i32`'s 4-byte alignment.
+        let struct_ = Type::structure(vec![Type::u8(), Type::i32()]);
+
+        assert_eq!(vec![0, 4], struct_.field_offsets());
+        assert_eq!(8, struct_.size());
+        assert_eq!(4, struct_.alignment());
+    }
+
+    #[test]
+    fn try_new_ok() {
+        let cif = Cif::try_new(vec![Type::i64(), Type::i64()].into_iter(),
+                               Type::i64())
+            .expect("a CIF over plain i64s should never be rejected");
+
+        let n: i64 = unsafe {
+            cif.call(CodePtr(add_it as *mut c_void), &[arg(&5i64), arg(&7i64)])
+        };
+        assert_eq!(12, n);
+    }
+
+    #[test]
+    fn try_new_variadic_err() {
+        // `try_new_variadic` surfaces a rejected vararg as an `Err`
+        // instead of panicking, so callers driving the CIF through the
+        // fallible API get a chance to handle it.
+        match Cif::try_new_variadic(Vec::<Type>::new().into_iter(),
+                                    vec![Type::i8()].into_iter(),
+                                    Type::i64())
+        {
+            Err(Error::UnpromotedVararg) => (),
+            other => panic!("expected Err(Error::UnpromotedVararg), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn call_builder_invoke() {
+        let n: i64 = unsafe {
+            CallBuilder::new()
+                .arg(Type::i64(), 5i64)
+                .arg(Type::i64(), 7i64)
+                .invoke(CodePtr(add_it as *mut c_void), Type::i64())
+        };
+
+        assert_eq!(12, n);
+    }
+
     #[test]
     fn closure() {
         let cif  = Cif::new(vec![Type::u64()].into_iter(), Type::u64());
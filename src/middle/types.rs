@@ -0,0 +1,323 @@
+//! Wraps C types for describing argument and result types to a
+//! [`Cif`](../struct.Cif.html).
+
+use std::cell::Cell;
+use std::mem;
+use std::ptr;
+
+use crate::low;
+
+use super::util::null_terminated;
+use super::Error;
+
+// libffi’s `ffi_type::type` tags (see `libffi/ffi.h`). These are part of
+// libffi’s stable ABI.
+const VOID: u16 = 0;
+const FLOAT: u16 = 2;
+#[allow(dead_code)]
+const DOUBLE: u16 = 3;
+const UINT8: u16 = 5;
+const SINT8: u16 = 6;
+const UINT16: u16 = 7;
+const SINT16: u16 = 8;
+const UINT32: u16 = 9;
+const SINT32: u16 = 10;
+const UINT64: u16 = 11;
+const SINT64: u16 = 12;
+const STRUCT: u16 = 13;
+const POINTER: u16 = 14;
+
+/// Represents a single C type.
+///
+/// This wraps a [`low::ffi_type`](../low/struct.ffi_type.html), taking
+/// care to keep any data it points to (e.g. a structure’s element types)
+/// alive for as long as the `Type` itself.
+///
+/// The `ffi_type` is boxed in a [`Cell`](std::cell::Cell) rather than a
+/// bare `Box`: libffi lazily fills in `size`/`alignment` by writing
+/// through the same pointer callers use to read this type's layout (see
+/// [`ensure_laid_out`](#method.ensure_laid_out)), and writing through a
+/// pointer derived from a shared `&Type` is only sound if that memory is
+/// actually interior-mutable. The `Box` still gives the `ffi_type` a
+/// stable heap address, since `elements` below (and any parent
+/// structure's `elements`) hold raw pointers into it that must survive
+/// this `Type` being moved.
+#[derive(Debug)]
+pub struct Type {
+    ffi_type: Box<Cell<low::ffi_type>>,
+    // Kept alive because `ffi_type.elements` points into it for
+    // structure types; `None` for primitive types.
+    elements: Option<Box<[*mut low::ffi_type]>>,
+    // The component types of a structure, retained so that their
+    // `ffi_type`s (referenced via `elements`) stay alive.
+    components: Vec<Type>,
+}
+
+impl Clone for Type {
+    fn clone(&self) -> Self {
+        if self.elements.is_some() {
+            Type::structure(self.components.iter().cloned())
+        } else {
+            Type::primitive(self.ffi_type.get().type_)
+        }
+    }
+}
+
+macro_rules! primitive_ctors {
+    ($( $name:ident => $tag:expr ),* $(,)?) => {
+        $(
+            /// Returns the
+            #[doc = stringify!($name)]
+            /// type.
+            pub fn $name() -> Self {
+                Type::primitive($tag)
+            }
+        )*
+    };
+}
+
+impl Type {
+    fn primitive(tag: u16) -> Self {
+        // libffi lays out only structures itself; a primitive `ffi_type`
+        // carries its size and alignment from the start.
+        let (size, alignment) = match tag {
+            VOID | UINT8 | SINT8 => (1, 1),
+            UINT16 | SINT16 => (2, mem::align_of::<u16>()),
+            UINT32 | SINT32 => (4, mem::align_of::<u32>()),
+            UINT64 | SINT64 => (8, mem::align_of::<u64>()),
+            FLOAT => (4, mem::align_of::<f32>()),
+            DOUBLE => (8, mem::align_of::<f64>()),
+            _ => (mem::size_of::<*const u8>(), mem::align_of::<*const u8>()),
+        };
+
+        Type {
+            ffi_type: Box::new(Cell::new(low::ffi_type {
+                size,
+                alignment: alignment as u16,
+                type_: tag,
+                elements: ptr::null_mut(),
+            })),
+            elements: None,
+            components: Vec::new(),
+        }
+    }
+
+    primitive_ctors! {
+        void => VOID,
+        u8 => UINT8,
+        i8 => SINT8,
+        u16 => UINT16,
+        i16 => SINT16,
+        u32 => UINT32,
+        i32 => SINT32,
+        u64 => UINT64,
+        i64 => SINT64,
+        f32 => FLOAT,
+        f64 => DOUBLE,
+        pointer => POINTER,
+    }
+
+    /// Constructs a structure type from the types of its fields.
+    pub fn structure<I>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = Type>,
+    {
+        let components: Vec<Type> = fields.into_iter().collect();
+        let raw_ptrs = components.iter().map(Type::as_raw_ptr).collect();
+        let elements = null_terminated(raw_ptrs);
+
+        let ffi_type = Box::new(Cell::new(low::ffi_type {
+            size: 0,
+            alignment: 0,
+            type_: STRUCT,
+            elements: elements.as_ptr() as *mut _,
+        }));
+
+        Type {
+            ffi_type,
+            elements: Some(elements),
+            components,
+        }
+    }
+
+    /// Gets a raw pointer to the underlying
+    /// [`low::ffi_type`](../low/struct.ffi_type.html).
+    ///
+    /// Obtained via [`Cell::as_ptr`](std::cell::Cell::as_ptr) rather than
+    /// by casting a shared reference, since libffi writes `size`/
+    /// `alignment` back through this same pointer (see
+    /// [`ensure_laid_out`](#method.ensure_laid_out)) and `Cell` is what
+    /// makes that write sound.
+    pub fn as_raw_ptr(&self) -> *mut low::ffi_type {
+        self.ffi_type.as_ptr()
+    }
+
+    /// libffi’s internal type tag, used to recognize promotion-sensitive
+    /// argument kinds (e.g. for variadic calls).
+    pub(crate) fn tag(&self) -> u16 {
+        self.ffi_type.get().type_
+    }
+
+    /// Whether this is an integer type narrower than a C `int`.
+    pub(crate) fn is_sub_int(&self) -> bool {
+        matches!(self.tag(), UINT8 | SINT8 | UINT16 | SINT16)
+    }
+
+    /// Whether this is one of libffi’s integer (or `bool`-like) scalar
+    /// types, as opposed to a float, pointer, structure, or `void`.
+    pub(crate) fn is_integer(&self) -> bool {
+        matches!(
+            self.tag(),
+            UINT8 | SINT8 | UINT16 | SINT16 | UINT32 | SINT32 | UINT64 | SINT64
+        )
+    }
+
+    /// Returns the size, in bytes, of this type, as computed by libffi.
+    ///
+    /// For primitive types this is known immediately; for a structure
+    /// type built with [`structure`](#method.structure) it is only
+    /// filled in lazily by libffi (normally as a side effect of
+    /// `ffi_prep_cif`), so this forces that computation first if it
+    /// hasn’t happened yet. See [`field_offsets`](#method.field_offsets)
+    /// for reading individual field offsets.
+    pub fn size(&self) -> usize {
+        self.ensure_laid_out();
+        self.ffi_type.get().size
+    }
+
+    /// Returns the alignment, in bytes, of this type, as computed by
+    /// libffi. See [`size`](#method.size) for when this becomes
+    /// available.
+    pub fn alignment(&self) -> usize {
+        self.ensure_laid_out();
+        self.ffi_type.get().alignment as usize
+    }
+
+    /// For a structure type, returns the byte offset of each field
+    /// within the structure, in declaration order, computed with the
+    /// usual C layout rules: each field is placed at the next offset
+    /// that satisfies its own alignment, and the overall structure size
+    /// (see [`size`](#method.size)) is rounded up to the structure’s
+    /// alignment.
+    ///
+    /// Returns an empty `Vec` for non-structure types.
+    pub fn field_offsets(&self) -> Vec<usize> {
+        self.ensure_laid_out();
+
+        let mut offsets = Vec::with_capacity(self.components.len());
+        let mut offset = 0usize;
+
+        for field in &self.components {
+            offset = round_up_to(offset, field.alignment());
+            offsets.push(offset);
+            offset += field.size();
+        }
+
+        offsets
+    }
+
+    /// Forces libffi to compute `size`/`alignment` for this type if it
+    /// has not already done so. libffi only fills these in lazily, so we
+    /// run a throwaway, argument-less `ffi_prep_cif` with this type as
+    /// the result type purely to trigger that computation — writing the
+    /// result back into the very same `ffi_type` through
+    /// [`as_raw_ptr`](#method.as_raw_ptr), which is why that memory lives
+    /// behind a [`Cell`](std::cell::Cell) instead of a bare `Box`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if libffi rejects the type (e.g. an empty structure), since
+    /// [`size`](#method.size)/[`alignment`](#method.alignment)/
+    /// [`field_offsets`](#method.field_offsets) have no way to report
+    /// that failure through their return types; silently leaving `size`
+    /// at `0` would make a bogus layout look like a legitimate one.
+    fn ensure_laid_out(&self) {
+        if self.ffi_type.get().size != 0 {
+            return;
+        }
+
+        let mut cif: low::ffi_cif = Default::default();
+        unsafe {
+            low::prep_cif(&mut cif,
+                          low::ffi_abi_FFI_DEFAULT_ABI,
+                          0,
+                          self.as_raw_ptr(),
+                          ptr::null_mut())
+        }.expect("low::prep_cif: failed to compute type layout");
+    }
+
+    /// Checks that this type is already promoted per the C
+    /// default-argument-promotion rules applied to arguments passed
+    /// through `...`: no bare `float` (must be `double`), and no integer
+    /// type narrower than `int`. See [`Error::UnpromotedVararg`] for why
+    /// this can't just promote the type itself.
+    pub(crate) fn check_vararg_promoted(&self) -> Result<(), Error> {
+        if self.tag() == FLOAT || self.is_sub_int() {
+            Err(Error::UnpromotedVararg)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Rounds `offset` up to the next multiple of `align` (per the usual C
+/// struct layout rules). `align` of `0` (only possible before libffi has
+/// laid the field's type out) leaves `offset` unchanged.
+fn round_up_to(offset: usize, align: usize) -> usize {
+    if align == 0 {
+        offset
+    } else {
+        (offset + align - 1) / align * align
+    }
+}
+
+/// An array of [`Type`](struct.Type.html)s, as used to describe a CIF’s
+/// argument types. Retains ownership of the `Type`s so that the raw
+/// `ffi_type` pointers handed to libffi stay valid.
+#[derive(Debug)]
+pub struct TypeArray {
+    elements: Box<[*mut low::ffi_type]>,
+    types: Vec<Type>,
+}
+
+impl Clone for TypeArray {
+    fn clone(&self) -> Self {
+        TypeArray::new(self.types.iter().cloned())
+    }
+}
+
+impl TypeArray {
+    /// Builds a `TypeArray` from the given types, in order.
+    pub fn new<I>(types: I) -> Self
+    where
+        I: Iterator<Item = Type>,
+    {
+        let types: Vec<Type> = types.collect();
+        let raw_ptrs = types.iter().map(Type::as_raw_ptr).collect();
+        let elements = null_terminated(raw_ptrs);
+
+        TypeArray { elements, types }
+    }
+
+    /// Gets a raw pointer to the underlying array of
+    /// [`low::ffi_type`](../low/struct.ffi_type.html) pointers, suitable
+    /// for `ffi_cif::arg_types`.
+    pub fn as_raw_ptr(&self) -> *mut *mut low::ffi_type {
+        self.elements.as_ptr() as *mut _
+    }
+
+    /// The individual types, in order.
+    pub fn elements(&self) -> &[Type] {
+        &self.types
+    }
+
+    /// The number of types in the array.
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Whether the array is empty.
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
@@ -0,0 +1,12 @@
+//! Internal helpers shared by the other `middle` submodules.
+
+/// Collects `raw_ptrs` into a null-terminated, heap-allocated slice.
+///
+/// libffi expects arrays of `*mut ffi_type` (a CIF's `arg_types`, or a
+/// structure's `elements`) to be terminated by a null pointer. Boxing the
+/// result gives it a stable address that survives the owning value being
+/// moved around.
+pub(crate) fn null_terminated<T>(mut raw_ptrs: Vec<*mut T>) -> Box<[*mut T]> {
+    raw_ptrs.push(std::ptr::null_mut());
+    raw_ptrs.into_boxed_slice()
+}